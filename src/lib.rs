@@ -0,0 +1,909 @@
+//! Library core for `pretty-rocm-smi`: collecting ROCm GPU metrics and
+//! rendering them, kept separate so the data can be harvested without
+//! spawning the ANSI formatter (e.g. embedding this crate in another tool).
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::process::Command;
+
+use chrono::Local;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+
+#[cfg(feature = "rsmi")]
+mod rsmi;
+
+// ── GPU snapshot ────────────────────────────────────────────────────────────
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct GpuSnapshot {
+    pub id: u32,
+    pub name: String,
+    pub gfx_ver: String,
+    pub temp: f64,
+    pub power: f64,
+    pub power_cap: f64,
+    /// `power / power_cap`, as a percentage (0 when `power_cap` is unknown).
+    pub power_pct: f64,
+    pub gpu_pct: f64,
+    pub vram_total: u64,
+    pub vram_used: u64,
+    pub vram_used_gib: f64,
+    /// Fan speed, as a percentage of max.
+    pub fan_pct: f64,
+    /// Core (graphics) clock, in MHz.
+    pub sclk_mhz: f64,
+    /// Memory clock, in MHz.
+    pub mclk_mhz: f64,
+    /// Estimated maximum PCIe bandwidth over the last second, in MB/s
+    /// (`rocm-smi --showbw` reports a single aggregate figure, not TX/RX).
+    pub pcie_bw_mbps: f64,
+    /// Populated only by [`attach_processes`] (i.e. `--processes` mode).
+    pub processes: Vec<GpuProcess>,
+}
+
+/// A process using a GPU, as reported by `rocm-smi --showpids`.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct GpuProcess {
+    pub pid: u32,
+    pub name: String,
+    pub vram_used: u64,
+    pub gpu_ids: Vec<u32>,
+}
+
+// ── History (for --watch graphs) ────────────────────────────────────────────
+
+const HISTORY_CAP: usize = 120;
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+#[derive(Debug, Default, Clone)]
+pub struct GpuHistory {
+    pub temp: VecDeque<f64>,
+    pub gpu_pct: VecDeque<f64>,
+    pub vram_pct: VecDeque<f64>,
+}
+
+fn push_capped(buf: &mut VecDeque<f64>, val: f64) {
+    buf.push_back(val);
+    while buf.len() > HISTORY_CAP {
+        buf.pop_front();
+    }
+}
+
+/// Append the latest sample from each snapshot into its per-GPU ring buffers.
+pub fn update_histories(histories: &mut HashMap<u32, GpuHistory>, gpus: &[GpuSnapshot]) {
+    for gpu in gpus {
+        let h = histories.entry(gpu.id).or_default();
+        push_capped(&mut h.temp, gpu.temp);
+        push_capped(&mut h.gpu_pct, gpu.gpu_pct);
+        let vram_pct = if gpu.vram_total > 0 {
+            100.0 * gpu.vram_used as f64 / gpu.vram_total as f64
+        } else {
+            0.0
+        };
+        push_capped(&mut h.vram_pct, vram_pct);
+    }
+}
+
+/// Render the last `width` samples of `history` as a block-glyph sparkline,
+/// scaled between the observed min/max. Truncates oldest samples to fit.
+fn sparkline(history: &VecDeque<f64>, width: usize) -> String {
+    if width == 0 {
+        return String::new();
+    }
+    if history.is_empty() {
+        return " ".repeat(width);
+    }
+    let min = history.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = history.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = (max - min).max(1e-6);
+
+    let visible: Vec<f64> = history.iter().rev().take(width).rev().cloned().collect();
+    let mut out = String::with_capacity(width);
+    for v in &visible {
+        let ratio = ((v - min) / range).clamp(0.0, 1.0);
+        let idx = (ratio * (BLOCKS.len() - 1) as f64).round() as usize;
+        out.push(BLOCKS[idx]);
+    }
+    let pad = width.saturating_sub(visible.len());
+    format!("{}{}", " ".repeat(pad), out)
+}
+
+// ── Data collection ─────────────────────────────────────────────────────────
+
+fn run_cmd(cmd: &str, args: &[&str]) -> String {
+    Command::new(cmd)
+        .args(args)
+        .output()
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default()
+}
+
+fn parse_float(s: &str) -> f64 {
+    let re = Regex::new(r"([\d.]+)").unwrap();
+    re.captures(s)
+        .and_then(|c| c.get(1))
+        .and_then(|m| m.as_str().parse().ok())
+        .unwrap_or(0.0)
+}
+
+pub fn driver_version() -> String {
+    let out = run_cmd("rocm-smi", &["--showdriver"]);
+    let re = Regex::new(r"Driver version:\s*(\S+)").unwrap();
+    re.captures(&out)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_else(|| "N/A".to_string())
+}
+
+pub fn rocm_version() -> String {
+    if let Ok(content) = std::fs::read_to_string("/opt/rocm/.info/version") {
+        let v = content.trim().split('-').next().unwrap_or("N/A");
+        if v.starts_with(|c: char| c.is_ascii_digit()) {
+            return v.to_string();
+        }
+    }
+    "N/A".to_string()
+}
+
+/// `--showfan`: `GPU[0] : Fan Level: 255 (100%)`.
+fn parse_fan(out: &str, gpus: &mut [GpuSnapshot]) {
+    let re = Regex::new(r"GPU\[(\d+)\]\s*:\s*Fan Level:\s*\d+\s*\((\d+)%\)").unwrap();
+    for cap in re.captures_iter(out) {
+        if let (Some(idx), Some(pct)) = (cap.get(1), cap.get(2)) {
+            if let (Ok(i), Ok(p)) = (idx.as_str().parse::<usize>(), pct.as_str().parse::<f64>()) {
+                if i < gpus.len() {
+                    gpus[i].fan_pct = p;
+                }
+            }
+        }
+    }
+}
+
+/// `--showgpuclocks`/`--showclocks`: `GPU[0] : sclk clock level: 6: (1502Mhz)`
+/// and the `mclk` equivalent (case of "Mhz" has varied across ROCm releases).
+fn parse_clocks(out: &str, gpus: &mut [GpuSnapshot]) {
+    let re_sclk = Regex::new(r"(?i)GPU\[(\d+)\]\s*:\s*sclk clock level:\s*\d+:\s*\((\d+)mhz\)").unwrap();
+    for cap in re_sclk.captures_iter(out) {
+        if let (Some(idx), Some(mhz)) = (cap.get(1), cap.get(2)) {
+            if let (Ok(i), Ok(m)) = (idx.as_str().parse::<usize>(), mhz.as_str().parse::<f64>()) {
+                if i < gpus.len() {
+                    gpus[i].sclk_mhz = m;
+                }
+            }
+        }
+    }
+
+    let re_mclk = Regex::new(r"(?i)GPU\[(\d+)\]\s*:\s*mclk clock level:\s*\d+:\s*\((\d+)mhz\)").unwrap();
+    for cap in re_mclk.captures_iter(out) {
+        if let (Some(idx), Some(mhz)) = (cap.get(1), cap.get(2)) {
+            if let (Ok(i), Ok(m)) = (idx.as_str().parse::<usize>(), mhz.as_str().parse::<f64>()) {
+                if i < gpus.len() {
+                    gpus[i].mclk_mhz = m;
+                }
+            }
+        }
+    }
+}
+
+/// `--showbw`: `GPU[0] : Estimated maximum PCIe bandwidth over the last
+/// second (MB/s): 15754.46`. `rocm-smi` only reports a single aggregate
+/// estimate (no separate TX/RX), so that's what we store.
+fn parse_bandwidth(out: &str, gpus: &mut [GpuSnapshot]) {
+    let re = Regex::new(r"(?i)GPU\[(\d+)\]\s*:\s*Estimated maximum PCIe bandwidth over the last second \(MB/s\):\s*([\d.]+)").unwrap();
+    for cap in re.captures_iter(out) {
+        if let (Some(idx), Some(mbps)) = (cap.get(1), cap.get(2)) {
+            if let (Ok(i), Ok(b)) = (idx.as_str().parse::<usize>(), mbps.as_str().parse::<f64>()) {
+                if i < gpus.len() {
+                    gpus[i].pcie_bw_mbps = b;
+                }
+            }
+        }
+    }
+}
+
+/// Genuinely static fields that rocm_smi_lib doesn't expose through the
+/// handful of calls the `rsmi` backend makes (name, gfx version, power cap).
+/// Fetched once via the text backend and cached, so the `--watch` hot path
+/// still avoids spawning `rocm-smi` on every frame for these.
+#[cfg(feature = "rsmi")]
+struct StaticFields {
+    name: String,
+    gfx_ver: String,
+    power_cap: f64,
+}
+
+#[cfg(feature = "rsmi")]
+static STATIC_FIELDS: std::sync::OnceLock<HashMap<u32, StaticFields>> = std::sync::OnceLock::new();
+
+#[cfg(feature = "rsmi")]
+fn static_fields() -> &'static HashMap<u32, StaticFields> {
+    STATIC_FIELDS.get_or_init(|| {
+        collect_text()
+            .into_iter()
+            .map(|g| {
+                (
+                    g.id,
+                    StaticFields {
+                        name: g.name,
+                        gfx_ver: g.gfx_ver,
+                        power_cap: g.power_cap,
+                    },
+                )
+            })
+            .collect()
+    })
+}
+
+/// Collect a fresh snapshot of every GPU visible to `rocm-smi`.
+///
+/// With the `rsmi` feature enabled and `librocm_smi64` available, temperature,
+/// power, VRAM and utilization are read from the library directly instead of
+/// by shelling out, which matters for `--watch`. Name, gfx version and power
+/// cap come from a one-time text scrape; fan, clocks and PCIe bandwidth are
+/// still scraped from `rocm-smi` on every call. Falls back to scraping
+/// `rocm-smi`'s text/JSON output for everything otherwise.
+pub fn collect() -> Vec<GpuSnapshot> {
+    #[cfg(feature = "rsmi")]
+    {
+        if let Some(mut gpus) = rsmi::collect() {
+            let statics = static_fields();
+            for gpu in &mut gpus {
+                if let Some(s) = statics.get(&gpu.id) {
+                    gpu.name = s.name.clone();
+                    gpu.gfx_ver = s.gfx_ver.clone();
+                    gpu.power_cap = s.power_cap;
+                }
+            }
+            // Fan, clocks, and PCIe bandwidth move with load, so they're not
+            // safe to cache like the static fields above — re-scrape them
+            // every frame rather than freezing the first sample forever.
+            let fan = run_cmd("rocm-smi", &["--showfan"]);
+            parse_fan(&fan, &mut gpus);
+            let clocks = run_cmd("rocm-smi", &["--showgpuclocks", "--showclocks"]);
+            parse_clocks(&clocks, &mut gpus);
+            let bw = run_cmd("rocm-smi", &["--showbw"]);
+            parse_bandwidth(&bw, &mut gpus);
+
+            for gpu in &mut gpus {
+                gpu.power_pct = if gpu.power_cap > 0.0 { 100.0 * gpu.power / gpu.power_cap } else { 0.0 };
+            }
+            return gpus;
+        }
+    }
+    collect_text()
+}
+
+fn collect_text() -> Vec<GpuSnapshot> {
+    let mut gpus: Vec<GpuSnapshot> = Vec::new();
+
+    let concise = run_cmd("rocm-smi", &[]);
+    for line in concise.lines() {
+        let line = line.trim();
+        if line.is_empty() || !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 15 {
+            continue;
+        }
+        if let Ok(id) = parts[0].parse::<u32>() {
+            gpus.push(GpuSnapshot {
+                id,
+                temp: parse_float(parts[4]),
+                power: parse_float(parts[5]),
+                power_cap: parse_float(parts[13]),
+                gpu_pct: if parts.len() > 15 {
+                    parse_float(parts[15])
+                } else {
+                    0.0
+                },
+                ..Default::default()
+            });
+        }
+    }
+
+    let vram_json = run_cmd("rocm-smi", &["--showmeminfo", "vram", "--json"]);
+    if let Ok(Value::Object(map)) = serde_json::from_str::<Value>(&vram_json) {
+        for (i, gpu) in gpus.iter_mut().enumerate() {
+            let key = format!("card{}", i);
+            if let Some(card) = map.get(&key) {
+                if let Some(total) = card.get("VRAM Total Memory (B)") {
+                    gpu.vram_total = total
+                        .as_str()
+                        .and_then(|s| s.parse().ok())
+                        .or_else(|| total.as_u64())
+                        .unwrap_or(0);
+                }
+                if let Some(used) = card.get("VRAM Total Used Memory (B)") {
+                    gpu.vram_used = used
+                        .as_str()
+                        .and_then(|s| s.parse().ok())
+                        .or_else(|| used.as_u64())
+                        .unwrap_or(0);
+                }
+            }
+        }
+    }
+
+    let prod = run_cmd("rocm-smi", &["--showproductname"]);
+    let re_name = Regex::new(r"GPU\[(\d+)\]\s*:\s*Card Series:\s*(.*)").unwrap();
+    for cap in re_name.captures_iter(&prod) {
+        if let (Some(idx), Some(name)) = (cap.get(1), cap.get(2)) {
+            if let Ok(i) = idx.as_str().parse::<usize>() {
+                if i < gpus.len() {
+                    gpus[i].name = name.as_str().trim().to_string();
+                }
+            }
+        }
+    }
+
+    let hw = run_cmd("rocm-smi", &["--showhw"]);
+    for line in hw.lines() {
+        let line = line.trim();
+        if line.is_empty() || !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() >= 10 {
+            if let Ok(id) = parts[0].parse::<usize>() {
+                if id < gpus.len() {
+                    gpus[id].gfx_ver = parts[4].to_string();
+                }
+            }
+        }
+    }
+
+    let fan = run_cmd("rocm-smi", &["--showfan"]);
+    parse_fan(&fan, &mut gpus);
+
+    let clocks = run_cmd("rocm-smi", &["--showgpuclocks", "--showclocks"]);
+    parse_clocks(&clocks, &mut gpus);
+
+    let bw = run_cmd("rocm-smi", &["--showbw"]);
+    parse_bandwidth(&bw, &mut gpus);
+
+    for gpu in &mut gpus {
+        gpu.power_pct = if gpu.power_cap > 0.0 { 100.0 * gpu.power / gpu.power_cap } else { 0.0 };
+        gpu.vram_used_gib = bytes_to_gib(gpu.vram_used);
+    }
+
+    gpus
+}
+
+/// Shell out for the raw process tables and hand them to [`parse_processes`].
+fn collect_processes() -> Vec<GpuProcess> {
+    let showpids = run_cmd("rocm-smi", &["--showpids"]);
+    let showpidgpus = run_cmd("rocm-smi", &["--showpidgpus"]);
+    parse_processes(&showpids, &showpidgpus)
+}
+
+/// Parse `--showpids`' process table, falling back to `--showpidgpus` (`PID
+/// N : GPU M` lines) for any process whose GPU list didn't come through in
+/// the main table.
+fn parse_processes(showpids: &str, showpidgpus: &str) -> Vec<GpuProcess> {
+    let mut procs: Vec<GpuProcess> = Vec::new();
+
+    for line in showpids.lines() {
+        let line = line.trim();
+        if line.is_empty() || !line.starts_with(|c: char| c.is_ascii_digit()) {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 4 {
+            continue;
+        }
+        let pid: u32 = match parts[0].parse() {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        let gpu_ids: Vec<u32> = parts[2]
+            .split(',')
+            .filter_map(|s| s.trim().parse().ok())
+            .collect();
+        procs.push(GpuProcess {
+            pid,
+            name: parts[1].to_string(),
+            vram_used: parts[3].parse().unwrap_or(0),
+            gpu_ids,
+        });
+    }
+
+    let missing: Vec<u32> = procs.iter().filter(|p| p.gpu_ids.is_empty()).map(|p| p.pid).collect();
+    if !missing.is_empty() {
+        let re = Regex::new(r"PID\s+(\d+)\s*:\s*GPU\s+(\d+)").unwrap();
+        for cap in re.captures_iter(showpidgpus) {
+            if let (Some(pid), Some(gpu)) = (cap.get(1), cap.get(2)) {
+                if let (Ok(pid), Ok(gpu)) = (pid.as_str().parse::<u32>(), gpu.as_str().parse::<u32>()) {
+                    if !missing.contains(&pid) {
+                        continue;
+                    }
+                    if let Some(p) = procs.iter_mut().find(|p| p.pid == pid) {
+                        if !p.gpu_ids.contains(&gpu) {
+                            p.gpu_ids.push(gpu);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    procs
+}
+
+/// Collect per-process GPU usage and attach each process to the snapshots of
+/// the GPUs it's running on (used by `--processes` mode).
+pub fn attach_processes(gpus: &mut [GpuSnapshot]) {
+    let procs = collect_processes();
+    for gpu in gpus.iter_mut() {
+        gpu.processes = procs
+            .iter()
+            .filter(|p| p.gpu_ids.contains(&gpu.id))
+            .cloned()
+            .collect();
+        gpu.processes.sort_by_key(|p| std::cmp::Reverse(p.vram_used));
+    }
+}
+
+// ── Helpers ─────────────────────────────────────────────────────────────────
+
+fn bytes_to_gib(b: u64) -> f64 {
+    b as f64 / (1024.0 * 1024.0 * 1024.0)
+}
+
+fn bytes_to_mib(b: u64) -> u64 {
+    b / (1024 * 1024)
+}
+
+/// Current terminal width in columns, falling back to `default` when it
+/// can't be determined (e.g. output is piped).
+pub fn terminal_width(default: usize) -> usize {
+    run_cmd("tput", &["cols"]).trim().parse().unwrap_or(default)
+}
+
+// ── Temperature units & thresholds ──────────────────────────────────────────
+
+/// All [`GpuSnapshot`] temperatures are collected and stored in Celsius;
+/// this only controls how they're displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TempUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TempUnit {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "c" | "celsius" => Some(TempUnit::Celsius),
+            "f" | "fahrenheit" => Some(TempUnit::Fahrenheit),
+            "k" | "kelvin" => Some(TempUnit::Kelvin),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TempUnit::Celsius => "°C",
+            TempUnit::Fahrenheit => "°F",
+            TempUnit::Kelvin => "K",
+        }
+    }
+
+    pub fn from_celsius(&self, c: f64) -> f64 {
+        match self {
+            TempUnit::Celsius => c,
+            TempUnit::Fahrenheit => c * 9.0 / 5.0 + 32.0,
+            TempUnit::Kelvin => c + 273.15,
+        }
+    }
+
+    pub fn to_celsius(&self, v: f64) -> f64 {
+        match self {
+            TempUnit::Celsius => v,
+            TempUnit::Fahrenheit => (v - 32.0) * 5.0 / 9.0,
+            TempUnit::Kelvin => v - 273.15,
+        }
+    }
+}
+
+/// Warn/critical cutoffs, always in Celsius internally. Build from
+/// user-supplied values (in whatever unit they were given) via
+/// `TempUnit::to_celsius` before constructing this.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempThresholds {
+    /// Below this, rows render GREEN ("cool").
+    pub cool_c: f64,
+    pub warn_c: f64,
+    pub critical_c: f64,
+}
+
+impl Default for TempThresholds {
+    fn default() -> Self {
+        TempThresholds { cool_c: 50.0, warn_c: 75.0, critical_c: 90.0 }
+    }
+}
+
+// ── ANSI colors ─────────────────────────────────────────────────────────────
+
+mod ansi {
+    pub const RESET: &str = "\x1b[0m";
+    pub const BOLD: &str = "\x1b[1m";
+    pub const DIM: &str = "\x1b[2m";
+    pub const RED: &str = "\x1b[91m";
+    pub const GREEN: &str = "\x1b[92m";
+    pub const YELLOW: &str = "\x1b[93m";
+    pub const CYAN: &str = "\x1b[96m";
+    pub const WHITE: &str = "\x1b[97m";
+}
+
+/// `val_c` is always Celsius, regardless of the unit chosen for display, so
+/// a user monitoring in Fahrenheit or Kelvin still gets correct transitions.
+fn ansi_temp(val_c: f64, thresholds: &TempThresholds) -> &'static str {
+    if val_c >= thresholds.critical_c { ansi::RED }
+    else if val_c >= thresholds.warn_c { ansi::YELLOW }
+    else if val_c >= thresholds.cool_c { ansi::WHITE }
+    else { ansi::GREEN }
+}
+
+fn ansi_ratio(ratio: f64) -> &'static str {
+    if ratio >= 0.9 { ansi::RED }
+    else if ratio >= 0.7 { ansi::YELLOW }
+    else if ratio > 0.0 { ansi::GREEN }
+    else { ansi::DIM }
+}
+
+/// Visible length of a string (strips ANSI escapes)
+fn vlen(s: &str) -> usize {
+    let re = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    re.replace_all(s, "").len()
+}
+
+fn rpad(s: &str, w: usize) -> String {
+    let vis = vlen(s);
+    if vis < w {
+        format!("{}{}", s, " ".repeat(w - vis))
+    } else {
+        s.to_string()
+    }
+}
+
+fn lpad(s: &str, w: usize) -> String {
+    let vis = vlen(s);
+    if vis < w {
+        format!("{}{}", " ".repeat(w - vis), s)
+    } else {
+        s.to_string()
+    }
+}
+
+// ── Structured output (--json / --csv) ──────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub timestamp: String,
+    pub driver: String,
+    pub rocm_version: String,
+    pub gpus: Vec<GpuSnapshot>,
+}
+
+/// Serialize a [`Report`] as pretty-printed JSON.
+pub fn to_json(gpus: &[GpuSnapshot], driver: &str, rocm: &str) -> String {
+    let report = Report {
+        timestamp: Local::now().to_rfc3339(),
+        driver: driver.to_string(),
+        rocm_version: rocm.to_string(),
+        gpus: gpus.to_vec(),
+    };
+    serde_json::to_string_pretty(&report).unwrap_or_default()
+}
+
+const CSV_HEADER: &str = "id,name,gfx_ver,temp,power,power_cap,power_pct,gpu_pct,vram_total,vram_used,vram_used_gib,fan_pct,sclk_mhz,mclk_mhz,pcie_bw_mbps";
+
+/// Quote a CSV field if it contains a comma, quote, or newline.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Render one CSV row per GPU under a stable header.
+pub fn to_csv(gpus: &[GpuSnapshot]) -> String {
+    let mut out = String::new();
+    out.push_str(CSV_HEADER);
+    out.push('\n');
+    for gpu in gpus {
+        let _ = writeln!(
+            out,
+            "{},{},{},{:.1},{:.1},{:.1},{:.1},{:.1},{},{},{:.2},{:.1},{:.0},{:.0},{:.2}",
+            gpu.id, csv_field(&gpu.name), csv_field(&gpu.gfx_ver), gpu.temp, gpu.power, gpu.power_cap,
+            gpu.power_pct, gpu.gpu_pct, gpu.vram_total, gpu.vram_used, gpu.vram_used_gib,
+            gpu.fan_pct, gpu.sclk_mhz, gpu.mclk_mhz, gpu.pcie_bw_mbps,
+        );
+    }
+    out
+}
+
+// ── Rendering ───────────────────────────────────────────────────────────────
+
+/// Knobs for [`render`]. `histories` is only consulted when `show_graphs` is
+/// set (i.e. `--watch` mode); pass an empty map otherwise.
+#[derive(Debug, Default, Clone)]
+pub struct RenderOptions {
+    pub width: usize,
+    pub driver: String,
+    pub rocm: String,
+    pub show_graphs: bool,
+    pub histories: HashMap<u32, GpuHistory>,
+    /// Render a per-GPU process table (populate snapshots via
+    /// [`attach_processes`] first).
+    pub show_processes: bool,
+    /// Render an extra row with fan speed, clocks, and PCIe bandwidth.
+    pub show_verbose: bool,
+    pub temp_unit: TempUnit,
+    pub temp_thresholds: TempThresholds,
+}
+
+/// Render a snapshot of all GPUs as the ANSI box report, returning the
+/// fully formatted string (caller decides whether to print it, log it, etc).
+pub fn render(gpus: &[GpuSnapshot], opts: &RenderOptions) -> String {
+    let w = opts.width;
+    let mut out = String::new();
+    let timestamp = Local::now().format("%a %b %d %H:%M:%S %Y").to_string();
+
+    let gpu_name = if !gpus.is_empty() { gpus[0].name.as_str() } else { "AMD GPU" };
+    let gfx_ver = if !gpus.is_empty() { gpus[0].gfx_ver.as_str() } else { "" };
+
+    // Header
+    let _ = writeln!(out, "{}╔{}╗{}", ansi::CYAN, "═".repeat(w), ansi::RESET);
+
+    let title = format!("{}{}pretty-rocm-smi{}", ansi::BOLD, ansi::CYAN, ansi::RESET);
+    let ts = format!("{}{}{}", ansi::DIM, timestamp, ansi::RESET);
+    let pad = w.saturating_sub(2 + "pretty-rocm-smi".len() + timestamp.len());
+    let _ = writeln!(out, "{}║{} {}{}{} {}║{}", ansi::CYAN, ansi::RESET, title, " ".repeat(pad), ts, ansi::CYAN, ansi::RESET);
+
+    let info = format!(
+        "{}{}{} {}({}){} {}Driver: {}{}{}  {}ROCm: {}{}{}",
+        ansi::BOLD, gpu_name, ansi::RESET,
+        ansi::DIM, gfx_ver, ansi::RESET,
+        ansi::DIM, ansi::RESET, ansi::BOLD, opts.driver,
+        ansi::DIM, ansi::RESET, ansi::BOLD, opts.rocm,
+    );
+    let info_plain = format!("{} ({}) Driver: {}  ROCm: {}", gpu_name, gfx_ver, opts.driver, opts.rocm);
+    let info_pad = w.saturating_sub(1 + info_plain.len());
+    let _ = writeln!(out, "{}║{} {}{}{}{}║{}", ansi::CYAN, ansi::RESET, info, ansi::RESET, " ".repeat(info_pad), ansi::CYAN, ansi::RESET);
+
+    let _ = writeln!(out, "{}╠{}╣{}", ansi::CYAN, "═".repeat(w), ansi::RESET);
+
+    // Column headers
+    let hdr = format!(
+        " {}{}GPU   Temp    Power Usage         VRAM Usage           GPU%{}",
+        ansi::BOLD, ansi::WHITE, ansi::RESET
+    );
+    let _ = writeln!(out, "{}║{}{}{}║{}", ansi::CYAN, ansi::RESET, rpad(&hdr, w), ansi::CYAN, ansi::RESET);
+    let _ = writeln!(out, "{}╟{}╢{}", ansi::CYAN, "─".repeat(w), ansi::RESET);
+
+    // GPU rows
+    for gpu in gpus {
+        let id_s = format!("{}{}{:>3}{}", ansi::BOLD, ansi::WHITE, gpu.id, ansi::RESET);
+
+        let tc = ansi_temp(gpu.temp, &opts.temp_thresholds);
+        let temp_s = format!("{}{:.0}{}{}", tc, opts.temp_unit.from_celsius(gpu.temp), opts.temp_unit.symbol(), ansi::RESET);
+
+        let pwr_ratio = if gpu.power_cap > 0.0 { gpu.power / gpu.power_cap } else { 0.0 };
+        let pc = ansi_ratio(pwr_ratio);
+        let power_s = format!(
+            "{}{:.0}W{} {}/ {:.0}W{}",
+            pc, gpu.power, ansi::RESET, ansi::DIM, gpu.power_cap, ansi::RESET
+        );
+
+        let vram_used_gib = bytes_to_gib(gpu.vram_used);
+        let vram_total_gib = bytes_to_gib(gpu.vram_total);
+        let vr = if gpu.vram_total > 0 { gpu.vram_used as f64 / gpu.vram_total as f64 } else { 0.0 };
+        let vc = ansi_ratio(vr);
+        let used_disp = if bytes_to_mib(gpu.vram_used) < 1024 {
+            format!("{}MiB", bytes_to_mib(gpu.vram_used))
+        } else {
+            format!("{:.1}GiB", vram_used_gib)
+        };
+        let vram_s = format!(
+            "{}{}{} {}/ {:.0}GiB{}",
+            vc, used_disp, ansi::RESET, ansi::DIM, vram_total_gib, ansi::RESET
+        );
+
+        let uc = ansi_ratio(gpu.gpu_pct / 100.0);
+        let bold = if gpu.gpu_pct >= 90.0 { ansi::BOLD } else { "" };
+        let util_s = format!("{}{}{:.0}%{}", uc, bold, gpu.gpu_pct, ansi::RESET);
+
+        let row = format!(
+            " {}   {}   {}   {}   {}",
+            lpad(&id_s, 3),
+            lpad(&temp_s, 5),
+            rpad(&power_s, 19),
+            rpad(&vram_s, 19),
+            lpad(&util_s, 4),
+        );
+
+        let _ = writeln!(out, "{}║{}{}{}║{}", ansi::CYAN, ansi::RESET, rpad(&row, w), ansi::CYAN, ansi::RESET);
+
+        if opts.show_graphs {
+            if let Some(h) = opts.histories.get(&gpu.id) {
+                let spark_w = 20usize.min(w.saturating_sub(30) / 3).max(4);
+                let temp_spark = sparkline(&h.temp, spark_w);
+                let util_spark = sparkline(&h.gpu_pct, spark_w);
+                let vram_spark = sparkline(&h.vram_pct, spark_w);
+
+                let graph_row = format!(
+                    "     {}temp{} {}{}{}   {}gpu%{} {}{}{}   {}vram{} {}{}{}",
+                    ansi::DIM, ansi::RESET, tc, temp_spark, ansi::RESET,
+                    ansi::DIM, ansi::RESET, uc, util_spark, ansi::RESET,
+                    ansi::DIM, ansi::RESET, vc, vram_spark, ansi::RESET,
+                );
+                let _ = writeln!(out, "{}║{}{}{}║{}", ansi::CYAN, ansi::RESET, rpad(&graph_row, w), ansi::CYAN, ansi::RESET);
+            }
+        }
+
+        if opts.show_verbose {
+            let fc = ansi_ratio(gpu.fan_pct / 100.0);
+            let verbose_row = format!(
+                "     {}Fan{} {}{:.0}%{}   {}SCLK{} {}{:.0}MHz{}   {}MCLK{} {}{:.0}MHz{}   {}PCIe{} {}{:.1}{} MB/s",
+                ansi::DIM, ansi::RESET, fc, gpu.fan_pct, ansi::RESET,
+                ansi::DIM, ansi::RESET, ansi::WHITE, gpu.sclk_mhz, ansi::RESET,
+                ansi::DIM, ansi::RESET, ansi::WHITE, gpu.mclk_mhz, ansi::RESET,
+                ansi::DIM, ansi::RESET, ansi::WHITE, gpu.pcie_bw_mbps, ansi::RESET,
+            );
+            let _ = writeln!(out, "{}║{}{}{}║{}", ansi::CYAN, ansi::RESET, rpad(&verbose_row, w), ansi::CYAN, ansi::RESET);
+        }
+
+        if opts.show_processes {
+            if gpu.processes.is_empty() {
+                let none_row = format!("     {}(no processes){}", ansi::DIM, ansi::RESET);
+                let _ = writeln!(out, "{}║{}{}{}║{}", ansi::CYAN, ansi::RESET, rpad(&none_row, w), ansi::CYAN, ansi::RESET);
+            } else {
+                for proc in &gpu.processes {
+                    let proc_row = format!(
+                        "     {}{:>7}{}  {}{:<20}{}  {}{:.2} GiB{}",
+                        ansi::WHITE, proc.pid, ansi::RESET,
+                        ansi::BOLD, proc.name, ansi::RESET,
+                        ansi::DIM, bytes_to_gib(proc.vram_used), ansi::RESET,
+                    );
+                    let _ = writeln!(out, "{}║{}{}{}║{}", ansi::CYAN, ansi::RESET, rpad(&proc_row, w), ansi::CYAN, ansi::RESET);
+                }
+            }
+        }
+    }
+
+    let _ = writeln!(out, "{}╚{}╝{}", ansi::CYAN, "═".repeat(w), ansi::RESET);
+
+    // Summary
+    let total_vram: u64 = gpus.iter().map(|g| g.vram_total).sum();
+    let used_vram: u64 = gpus.iter().map(|g| g.vram_used).sum();
+    let total_power: f64 = gpus.iter().map(|g| g.power).sum();
+    let total_cap: f64 = gpus.iter().map(|g| g.power_cap).sum();
+    let temps: Vec<f64> = gpus.iter().map(|g| g.temp).collect();
+    let avg_temp = if !temps.is_empty() { temps.iter().sum::<f64>() / temps.len() as f64 } else { 0.0 };
+
+    let tc = ansi_temp(avg_temp, &opts.temp_thresholds);
+    let pr = if total_cap > 0.0 { total_power / total_cap } else { 0.0 };
+    let pc = ansi_ratio(pr);
+
+    let _ = writeln!(
+        out,
+        " {}Total:{} {}{}{} GPUs  {}│{}  VRAM: {:.1}/{:.0} GiB  {}│{}  Power: {}{:.0}W{}{}/{:.0}W  {}│{}  Avg Temp: {}{:.0}{}{}",
+        ansi::DIM, ansi::RESET,
+        ansi::BOLD, gpus.len(), ansi::RESET,
+        ansi::DIM, ansi::RESET,
+        bytes_to_gib(used_vram), bytes_to_gib(total_vram),
+        ansi::DIM, ansi::RESET,
+        pc, total_power, ansi::RESET, ansi::DIM, total_cap,
+        ansi::DIM, ansi::RESET,
+        tc, opts.temp_unit.from_celsius(avg_temp), opts.temp_unit.symbol(), ansi::RESET,
+    );
+
+    if opts.show_graphs {
+        let _ = writeln!(out, "{}press Ctrl+C to exit{}", ansi::DIM, ansi::RESET);
+    }
+
+    out
+}
+
+// ── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gpu(id: u32) -> GpuSnapshot {
+        GpuSnapshot { id, ..Default::default() }
+    }
+
+    #[test]
+    fn parse_fan_reads_percent_from_real_output() {
+        let out = "GPU[0]		: Fan Level: 255 (100%)\nGPU[1]		: Fan Level: 128 (50%)\n";
+        let mut gpus = vec![gpu(0), gpu(1)];
+        parse_fan(out, &mut gpus);
+        assert_eq!(gpus[0].fan_pct, 100.0);
+        assert_eq!(gpus[1].fan_pct, 50.0);
+    }
+
+    #[test]
+    fn parse_clocks_reads_sclk_and_mclk_case_insensitively() {
+        let out = "GPU[0]		: sclk clock level: 6: (1502Mhz)\nGPU[0]		: mclk clock level: 3: (1200MHZ)\n";
+        let mut gpus = vec![gpu(0)];
+        parse_clocks(out, &mut gpus);
+        assert_eq!(gpus[0].sclk_mhz, 1502.0);
+        assert_eq!(gpus[0].mclk_mhz, 1200.0);
+    }
+
+    #[test]
+    fn parse_bandwidth_reads_single_aggregate_estimate() {
+        let out = "GPU[0]		: Estimated maximum PCIe bandwidth over the last second (MB/s): 15754.46\n";
+        let mut gpus = vec![gpu(0)];
+        parse_bandwidth(out, &mut gpus);
+        assert_eq!(gpus[0].pcie_bw_mbps, 15754.46);
+    }
+
+    #[test]
+    fn parse_processes_reads_showpids_table() {
+        let showpids = "PID  Process Name  GPU(s)  VRAM Used (B)\n1234  python  0  1073741824\n";
+        let procs = parse_processes(showpids, "");
+        assert_eq!(procs.len(), 1);
+        assert_eq!(procs[0].pid, 1234);
+        assert_eq!(procs[0].name, "python");
+        assert_eq!(procs[0].gpu_ids, vec![0]);
+        assert_eq!(procs[0].vram_used, 1_073_741_824);
+    }
+
+    #[test]
+    fn parse_processes_falls_back_to_showpidgpus_when_gpu_list_missing() {
+        let showpids = "PID  Process Name  GPU(s)  VRAM Used (B)\n1234  python  -  1073741824\n";
+        let showpidgpus = "PID 1234 : GPU 0\nPID 1234 : GPU 1\n";
+        let procs = parse_processes(showpids, showpidgpus);
+        assert_eq!(procs.len(), 1);
+        assert_eq!(procs[0].gpu_ids, vec![0, 1]);
+    }
+
+    #[test]
+    fn to_csv_renders_header_and_one_row_per_gpu() {
+        let mut g = gpu(0);
+        g.name = "Instinct MI300X".to_string();
+        g.temp = 55.0;
+        g.power = 250.0;
+        g.power_cap = 500.0;
+        g.power_pct = 50.0;
+        g.vram_total = 1 << 30;
+        g.vram_used = 1 << 29;
+        g.vram_used_gib = 0.5;
+        let csv = to_csv(&[g]);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_HEADER));
+        let row = lines.next().unwrap();
+        assert!(row.starts_with("0,Instinct MI300X,,55.0,250.0,500.0,50.0,"));
+    }
+
+    #[test]
+    fn to_csv_quotes_fields_with_commas() {
+        let mut g = gpu(0);
+        g.name = "Radeon, Pro".to_string();
+        let csv = to_csv(&[g]);
+        assert!(csv.contains("\"Radeon, Pro\""));
+    }
+
+    #[test]
+    fn to_json_embeds_gpu_fields() {
+        let mut g = gpu(7);
+        g.name = "Instinct MI300X".to_string();
+        g.temp = 42.0;
+        let json = to_json(&[g], "6.8.5", "6.2.0");
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["driver"], "6.8.5");
+        assert_eq!(value["rocm_version"], "6.2.0");
+        assert_eq!(value["gpus"][0]["id"], 7);
+        assert_eq!(value["gpus"][0]["name"], "Instinct MI300X");
+        assert_eq!(value["gpus"][0]["temp"], 42.0);
+    }
+}
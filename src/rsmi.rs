@@ -0,0 +1,112 @@
+//! Direct bindings to `librocm_smi64`, used instead of shelling out to
+//! `rocm-smi` when the `rsmi` feature is enabled. Only a thin slice of the
+//! C API is bound — just enough to populate a [`GpuSnapshot`].
+
+use std::os::raw::{c_int, c_uint};
+
+use crate::GpuSnapshot;
+
+const RSMI_STATUS_SUCCESS: c_int = 0;
+
+// RSMI_TEMP_TYPE_EDGE
+const RSMI_TEMP_TYPE_EDGE: u32 = 0;
+// RSMI_TEMP_CURRENT
+const RSMI_TEMP_CURRENT: i32 = 0;
+// RSMI_MEM_TYPE_VRAM
+const RSMI_MEM_TYPE_VRAM: c_int = 0;
+
+#[link(name = "rocm_smi64")]
+extern "C" {
+    fn rsmi_init(flags: u64) -> c_int;
+    fn rsmi_shut_down() -> c_int;
+    fn rsmi_num_monitor_devices(num_devices: *mut c_uint) -> c_int;
+    fn rsmi_dev_temp_metric_get(dv_ind: c_uint, sensor_type: u32, metric: i32, temperature: *mut i64) -> c_int;
+    fn rsmi_dev_power_ave_get(dv_ind: c_uint, sensor_ind: c_uint, power: *mut u64) -> c_int;
+    fn rsmi_dev_memory_usage_get(dv_ind: c_uint, mem_type: c_int, used: *mut u64) -> c_int;
+    fn rsmi_dev_memory_total_get(dv_ind: c_uint, mem_type: c_int, total: *mut u64) -> c_int;
+    fn rsmi_dev_busy_percent_get(dv_ind: c_uint, busy_percent: *mut u32) -> c_int;
+}
+
+struct Handle;
+
+impl Handle {
+    fn new() -> Option<Self> {
+        let rc = unsafe { rsmi_init(0) };
+        (rc == RSMI_STATUS_SUCCESS).then_some(Handle)
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe {
+            rsmi_shut_down();
+        }
+    }
+}
+
+/// Collect snapshots via `librocm_smi64`. Returns `None` if the library
+/// can't be initialized (not installed, no permissions, no devices) so the
+/// caller can fall back to the text-scraping backend.
+///
+/// Only covers the metrics with a direct rocm_smi_lib call (temp, power,
+/// VRAM, utilization); `lib.rs::collect()` fills in the rest: name, gfx_ver
+/// and power_cap from a cached text-backend snapshot (see `static_fields()`
+/// there), and fan, clocks and PCIe bandwidth by scraping `rocm-smi` each call.
+pub(crate) fn collect() -> Option<Vec<GpuSnapshot>> {
+    let _handle = Handle::new()?;
+
+    let mut num_devices: c_uint = 0;
+    if unsafe { rsmi_num_monitor_devices(&mut num_devices) } != RSMI_STATUS_SUCCESS {
+        return None;
+    }
+
+    let mut gpus = Vec::with_capacity(num_devices as usize);
+    for dv_ind in 0..num_devices {
+        let mut temp_millic: i64 = 0;
+        let temp = if unsafe { rsmi_dev_temp_metric_get(dv_ind, RSMI_TEMP_TYPE_EDGE, RSMI_TEMP_CURRENT, &mut temp_millic) }
+            == RSMI_STATUS_SUCCESS
+        {
+            temp_millic as f64 / 1000.0
+        } else {
+            0.0
+        };
+
+        let mut power_uw: u64 = 0;
+        let power = if unsafe { rsmi_dev_power_ave_get(dv_ind, 0, &mut power_uw) } == RSMI_STATUS_SUCCESS {
+            power_uw as f64 / 1_000_000.0
+        } else {
+            0.0
+        };
+
+        let mut vram_used: u64 = 0;
+        let _ = unsafe { rsmi_dev_memory_usage_get(dv_ind, RSMI_MEM_TYPE_VRAM, &mut vram_used) };
+
+        let mut vram_total: u64 = 0;
+        let _ = unsafe { rsmi_dev_memory_total_get(dv_ind, RSMI_MEM_TYPE_VRAM, &mut vram_total) };
+
+        let mut busy_pct: u32 = 0;
+        let gpu_pct = if unsafe { rsmi_dev_busy_percent_get(dv_ind, &mut busy_pct) } == RSMI_STATUS_SUCCESS {
+            busy_pct as f64
+        } else {
+            0.0
+        };
+
+        gpus.push(GpuSnapshot {
+            id: dv_ind,
+            temp,
+            power,
+            gpu_pct,
+            vram_total,
+            vram_used,
+            ..Default::default()
+        });
+    }
+
+    // power_cap isn't known yet here (the caller merges it in from the
+    // static-field cache), so power_pct is computed once there instead.
+    for gpu in &mut gpus {
+        gpu.vram_used_gib = gpu.vram_used as f64 / (1024.0 * 1024.0 * 1024.0);
+    }
+
+    Some(gpus)
+}